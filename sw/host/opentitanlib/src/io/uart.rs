@@ -0,0 +1,97 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use thiserror::Error;
+
+/// Errors produced by `Uart` implementations.
+#[derive(Error, Debug)]
+pub enum UartError {
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+    #[error("Invalid baudrate: {0}")]
+    InvalidBaudrate(u32),
+}
+
+/// Parity bit setting for a UART frame.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Number of data bits per UART frame.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DataBits {
+    Seven,
+    Eight,
+    Nine,
+}
+
+/// Number of stop bits per UART frame.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// The frame format of a UART link: data bits, parity, and stop bits.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct UartFraming {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl Default for UartFraming {
+    fn default() -> Self {
+        UartFraming {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+}
+
+/// A UART transport, as seen by the host side of a debug/rescue link.
+pub trait Uart {
+    /// Copies whatever received bytes are available into `buf` without
+    /// blocking, and returns how many were copied (possibly zero).
+    fn read(&self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Writes `buf` to the UART, blocking until all bytes are queued.
+    fn write(&self, buf: &[u8]) -> Result<()>;
+
+    /// Reports the baudrate currently configured on this UART.
+    fn get_baudrate(&self) -> Result<u32>;
+
+    /// Reconfigures the UART for the given baudrate.
+    fn set_baudrate(&self, baudrate: u32) -> Result<()>;
+
+    /// Asserts or deasserts a serial break condition on the line.
+    fn set_break(&self, enable: bool) -> Result<()>;
+
+    /// Clears bytes already buffered on the receive side.
+    fn clear_rx_buffer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Enables or disables hardware RTS/CTS flow control on this UART, so
+    /// that the transmitter pauses whenever the peer deasserts CTS. Most
+    /// transports wire this straight through to the underlying USB/serial
+    /// adapter; transports that cannot drive the modem control lines should
+    /// return `UartError::Unsupported` rather than silently ignoring it.
+    fn set_flow_control(&self, _enable: bool) -> Result<()> {
+        Err(UartError::Unsupported("hardware flow control".into()).into())
+    }
+
+    /// Reconfigures the frame format (data bits, parity, stop bits) used by
+    /// this UART. Transports that cannot drive an unsupported combination
+    /// should return `UartError::Unsupported` rather than silently using the
+    /// nearest format they can manage.
+    fn set_framing(&self, _framing: UartFraming) -> Result<()> {
+        Err(UartError::Unsupported("UART framing configuration".into()).into())
+    }
+}
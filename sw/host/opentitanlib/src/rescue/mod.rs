@@ -0,0 +1,43 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::app::TransportWrapper;
+
+pub mod serial;
+pub mod xmodem;
+
+/// A four-character mode code exchanged with the rescue firmware, e.g.
+/// `b"BAUD"` or `b"REBO"`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RescueMode(pub u32);
+
+/// Errors reported by a `Rescue` implementation.
+#[derive(Error, Debug)]
+pub enum RescueError {
+    #[error("Bad mode: {0}")]
+    BadMode(String),
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+}
+
+/// The host side of the boot ROM / ROM_EXT rescue protocol.
+pub trait Rescue {
+    /// Triggers rescue mode on the target and waits for it to respond.
+    fn enter(&self, transport: &TransportWrapper, reset_target: bool) -> Result<()>;
+    /// Changes the link speed, on both the target and the host side.
+    fn set_speed(&self, baud: u32) -> Result<()>;
+    /// Requests that the target switch to `mode`.
+    fn set_mode(&self, mode: RescueMode) -> Result<()>;
+    /// Requests that the target wait for further commands.
+    fn wait(&self) -> Result<()>;
+    /// Requests that the target reboot.
+    fn reboot(&self) -> Result<()>;
+    /// Sends `data` to the target.
+    fn send(&self, data: &[u8]) -> Result<()>;
+    /// Receives data from the target.
+    fn recv(&self) -> Result<Vec<u8>>;
+}
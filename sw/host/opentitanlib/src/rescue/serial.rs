@@ -7,7 +7,7 @@ use std::rc::Rc;
 use std::time::Duration;
 
 use crate::app::TransportWrapper;
-use crate::io::uart::Uart;
+use crate::io::uart::{Uart, UartFraming};
 use crate::rescue::xmodem::Xmodem;
 use crate::rescue::{Rescue, RescueError, RescueMode};
 use crate::uart::console::UartConsole;
@@ -16,6 +16,68 @@ pub struct RescueSerial {
     uart: Rc<dyn Uart>,
     reset_delay: Duration,
     enter_delay: Duration,
+    flow_control: bool,
+    framing: Option<UartFraming>,
+}
+
+/// Builds a `RescueSerial`, optionally turning on hardware RTS/CTS flow
+/// control and/or a non-default frame format before the rescue handshake
+/// begins.
+pub struct RescueSerialBuilder {
+    uart: Rc<dyn Uart>,
+    flow_control: bool,
+    framing: Option<UartFraming>,
+}
+
+impl RescueSerialBuilder {
+    pub fn new(uart: Rc<dyn Uart>) -> Self {
+        RescueSerialBuilder {
+            uart,
+            flow_control: false,
+            framing: None,
+        }
+    }
+
+    /// Enables hardware RTS/CTS flow control, so the `Xmodem` engine backs
+    /// off whenever the target deasserts CTS instead of overrunning its
+    /// receive buffer at the faster rescue baud rates.
+    pub fn flow_control(mut self, enable: bool) -> Self {
+        self.flow_control = enable;
+        self
+    }
+
+    /// Configures the frame format (parity, data bits, stop bits) used on
+    /// the link, for rescue bring-up over harnesses that don't accept the
+    /// default 8N1.
+    pub fn framing(mut self, framing: UartFraming) -> Self {
+        self.framing = Some(framing);
+        self
+    }
+
+    /// Applies the requested options and builds the `RescueSerial`. Fails
+    /// with `RescueError::Unsupported` if the underlying transport cannot
+    /// honor a requested option.
+    pub fn build(self) -> Result<RescueSerial> {
+        if self.flow_control {
+            self.uart.set_flow_control(true).map_err(|_| {
+                RescueError::Unsupported(
+                    "transport does not support RTS/CTS flow control".into(),
+                )
+            })?;
+        }
+        if let Some(framing) = self.framing {
+            self.uart.set_framing(framing).map_err(|_| {
+                RescueError::Unsupported("transport does not support the requested UART framing".into())
+            })?;
+        }
+        Ok(RescueSerial {
+            uart: self.uart,
+            reset_delay: Duration::from_millis(50),
+            enter_delay: Duration::from_secs(5),
+            flow_control: self.flow_control,
+            framing: self.framing,
+        })
+    }
 }
 
 impl RescueSerial {
@@ -32,11 +94,61 @@ impl RescueSerial {
     const BAUD_1M50: [u8; 4] = *b"1M50";
 
     pub fn new(uart: Rc<dyn Uart>) -> Self {
-        RescueSerial {
-            uart,
-            reset_delay: Duration::from_millis(50),
-            enter_delay: Duration::from_secs(5),
+        RescueSerialBuilder::new(uart)
+            .build()
+            .expect("default RescueSerial construction has no fallible options")
+    }
+
+    /// Known rescue link rates, fastest first.
+    const BAUD_CANDIDATES: [u32; 6] = [1_500_000, 1_333_333, 921_600, 460_800, 230_400, 115_200];
+
+    /// After entering rescue mode, walks the known `BAUD_*` rates from
+    /// fastest to slowest, asking the target to switch and confirming the
+    /// link at each candidate rate. Stops at (and returns) the first, and
+    /// therefore fastest, rate the target acknowledges; reverts cleanly to
+    /// the current rate and keeps it if the target never acknowledges a
+    /// faster one. Callers that don't know the target's capabilities up
+    /// front should call this right after `enter` instead of a fixed
+    /// `set_speed`.
+    pub fn negotiate_max_speed(&self) -> Result<u32> {
+        let baseline = self.uart.get_baudrate()?;
+        for &candidate in Self::BAUD_CANDIDATES.iter().filter(|&&baud| baud > baseline) {
+            if self.probe_speed(candidate)? {
+                log::info!("rescue link negotiated to {candidate} baud");
+                return Ok(candidate);
+            }
+        }
+        log::info!("target did not ack a faster rate; staying at {baseline} baud");
+        Ok(baseline)
+    }
+
+    /// Attempts to switch the link to `baud`, returning whether the target
+    /// confirmed a working round trip at that rate. Both an explicit
+    /// `error:` echo to the symbol and a timeout on the post-switch
+    /// confirmation (the usual symptom of a rate the physical link can't
+    /// sustain) count as a declined probe here. `set_speed` leaves the host
+    /// side at the last-known-good rate in either failure case: on an
+    /// `error:` echo it never switches, and on a failed confirmation it
+    /// switches back before returning.
+    fn probe_speed(&self, baud: u32) -> Result<bool> {
+        match self.set_speed(baud) {
+            Ok(()) => Ok(true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Switches the host side of the link to `baud`, reapplying whatever
+    /// flow control and frame format were configured in case reopening the
+    /// port reset them to the transport's defaults.
+    fn apply_link_settings(&self, baud: u32) -> Result<()> {
+        self.uart.set_baudrate(baud)?;
+        if self.flow_control {
+            self.uart.set_flow_control(true)?;
+        }
+        if let Some(framing) = self.framing {
+            self.uart.set_framing(framing)?;
         }
+        Ok(())
     }
 }
 
@@ -50,9 +162,10 @@ impl Rescue for RescueSerial {
         UartConsole::wait_for(&*self.uart, r"rescue:.*\r\n", self.enter_delay)?;
         log::info!("Rescue triggered. clearing serial break.");
         self.uart.set_break(false)?;
-        // Upon entry, rescue is going to tell us what mode it is.
-        // Consume and discard.
-        let _ = UartConsole::wait_for(&*self.uart, r"(ok|error):.*\r\n", Self::ONE_SECOND);
+        // Upon entry, rescue is going to tell us what mode it is. Consume
+        // and discard, reading until the line goes idle rather than
+        // guessing a timeout.
+        let _ = UartConsole::wait_for_idle(&*self.uart, r"(ok|error):.*\r\n", Self::ONE_SECOND);
         Ok(())
     }
 
@@ -67,18 +180,34 @@ impl Rescue for RescueSerial {
             1500000 => Self::BAUD_1M50,
             _ => return Err(RescueError::BadMode(format!("Unsupported badrate {baud}")).into()),
         };
+        let baseline = self.uart.get_baudrate()?;
 
         // Request to change rates.
         self.set_mode(Self::BAUD)?;
 
-        // Send the new rate and check for success.
+        // Send the new rate and check that the target recognized the
+        // symbol. This is only an echo at the *current* rate: it tells us
+        // the target knows the symbol, not that the physical link can
+        // sustain it.
         self.uart.write(&symbol)?;
-        let result = UartConsole::wait_for(&*self.uart, r"(ok|error):.*\r\n", Self::ONE_SECOND)?;
+        let result = UartConsole::wait_for_idle(&*self.uart, r"(ok|error):.*\r\n", Self::ONE_SECOND)?;
         if result[1] == "error" {
             return Err(RescueError::BadMode(result[0].clone()).into());
         }
-        // Change our side of the connection to the new rate.
-        self.uart.set_baudrate(baud)?;
+        // Change our side of the connection to the new rate, preserving
+        // whatever flow control and frame format were configured in case
+        // reopening the port reset them to the transport's defaults.
+        self.apply_link_settings(baud)?;
+
+        // Confirm the link actually works at the new rate with a clean
+        // round trip; a rate the firmware merely recognizes but the
+        // physical link can't sustain will show up here as a timeout
+        // instead of the echoed `mode: WAIT` line. Revert to the
+        // last-known-good rate rather than leaving the host wedged.
+        if let Err(e) = self.set_mode(Self::WAIT) {
+            self.apply_link_settings(baseline)?;
+            return Err(e);
+        }
         Ok(())
     }
 
@@ -88,7 +217,7 @@ impl Rescue for RescueSerial {
         let enter = b'\r';
         self.uart.write(std::slice::from_ref(&enter))?;
         let mode = std::str::from_utf8(&mode)?;
-        let result = UartConsole::wait_for(
+        let result = UartConsole::wait_for_idle(
             &*self.uart,
             &format!("mode: {mode}\r\n(ok|error):.*\r\n"),
             Self::ONE_SECOND,
@@ -111,15 +240,219 @@ impl Rescue for RescueSerial {
     }
 
     fn send(&self, data: &[u8]) -> Result<()> {
-        let xm = Xmodem::new();
+        let xm = Xmodem::ymodem();
         xm.send(&*self.uart, data)?;
         Ok(())
     }
 
     fn recv(&self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
-        let xm = Xmodem::new();
+        let xm = Xmodem::ymodem();
         xm.receive(&*self.uart, &mut data)?;
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::uart::{DataBits, Parity, StopBits};
+    use std::cell::{Cell, RefCell};
+    use std::collections::{HashSet, VecDeque};
+
+    /// A synchronous fake rescue target: every `write` immediately queues
+    /// the reply bytes `set_mode`/`set_speed` expect to read back, so no
+    /// real concurrency is needed to drive `RescueSerial` against it.
+    struct FakeTarget {
+        baudrate: Cell<u32>,
+        accepted: HashSet<u32>,
+        // Rates the target acks the 4-char symbol for, but then can't
+        // actually sustain: simulates a physical link that drops out once
+        // switched, as opposed to one that never agreed to switch at all.
+        unsustainable: HashSet<u32>,
+        silent: Cell<bool>,
+        pending: RefCell<Vec<u8>>,
+        expect_symbol: Cell<bool>,
+        reply: RefCell<VecDeque<u8>>,
+        framing_calls: RefCell<Vec<UartFraming>>,
+        flow_control_calls: RefCell<Vec<bool>>,
+    }
+
+    impl FakeTarget {
+        fn new(initial_baud: u32, accepted: &[u32]) -> Self {
+            Self::with_unsustainable(initial_baud, accepted, &[])
+        }
+
+        fn with_unsustainable(initial_baud: u32, accepted: &[u32], unsustainable: &[u32]) -> Self {
+            FakeTarget {
+                baudrate: Cell::new(initial_baud),
+                accepted: accepted.iter().copied().collect(),
+                unsustainable: unsustainable.iter().copied().collect(),
+                silent: Cell::new(false),
+                pending: RefCell::new(Vec::new()),
+                expect_symbol: Cell::new(false),
+                reply: RefCell::new(VecDeque::new()),
+                framing_calls: RefCell::new(Vec::new()),
+                flow_control_calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn symbol_to_baud(symbol: &[u8]) -> Option<u32> {
+            match symbol {
+                b"115K" => Some(115200),
+                b"230K" => Some(230400),
+                b"460K" => Some(460800),
+                b"921K" => Some(921600),
+                b"1M33" => Some(1333333),
+                b"1M50" => Some(1500000),
+                _ => None,
+            }
+        }
+    }
+
+    impl Uart for FakeTarget {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut reply = self.reply.borrow_mut();
+            let n = reply.len().min(buf.len());
+            for (i, byte) in reply.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, buf: &[u8]) -> Result<()> {
+            if self.silent.get() {
+                return Ok(());
+            }
+
+            if self.expect_symbol.get() {
+                self.expect_symbol.set(false);
+                let baud = Self::symbol_to_baud(buf);
+                let accepted = baud.map(|b| self.accepted.contains(&b)).unwrap_or(false);
+                let mut reply = self.reply.borrow_mut();
+                if accepted {
+                    reply.extend(b"ok: speed\r\n");
+                } else {
+                    reply.extend(b"error: speed\r\n");
+                }
+                drop(reply);
+                if accepted && baud.is_some_and(|b| self.unsustainable.contains(&b)) {
+                    // The symbol was recognized, but once the host actually
+                    // switches to this rate the link goes silent: nothing
+                    // further gets through, including the post-switch
+                    // confirmation, until the host switches back to a rate
+                    // the link can carry (see `set_baudrate`).
+                    self.silent.set(true);
+                }
+                return Ok(());
+            }
+
+            let mut pending = self.pending.borrow_mut();
+            pending.extend_from_slice(buf);
+            if pending.len() >= 5 && pending[4] == b'\r' {
+                let mode = String::from_utf8_lossy(&pending[..4]).to_string();
+                pending.clear();
+                self.reply
+                    .borrow_mut()
+                    .extend(format!("mode: {mode}\r\nok: mode\r\n").as_bytes());
+                if mode == "BAUD" {
+                    self.expect_symbol.set(true);
+                }
+            }
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(self.baudrate.get())
+        }
+
+        fn set_baudrate(&self, baudrate: u32) -> Result<()> {
+            self.baudrate.set(baudrate);
+            self.silent.set(self.unsustainable.contains(&baudrate));
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_flow_control(&self, enable: bool) -> Result<()> {
+            self.flow_control_calls.borrow_mut().push(enable);
+            Ok(())
+        }
+
+        fn set_framing(&self, framing: UartFraming) -> Result<()> {
+            self.framing_calls.borrow_mut().push(framing);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn negotiate_max_speed_picks_fastest_accepted_rate() {
+        let target = FakeTarget::new(115_200, &[115_200, 230_400, 460_800]);
+        let rescue = RescueSerial::new(Rc::new(target));
+        let negotiated = rescue
+            .negotiate_max_speed()
+            .expect("negotiation should succeed");
+        assert_eq!(negotiated, 460_800);
+    }
+
+    #[test]
+    fn negotiate_max_speed_keeps_baseline_when_nothing_faster_is_accepted() {
+        let target = FakeTarget::new(115_200, &[115_200]);
+        let rescue = RescueSerial::new(Rc::new(target));
+        let negotiated = rescue
+            .negotiate_max_speed()
+            .expect("negotiation should succeed");
+        assert_eq!(negotiated, 115_200);
+    }
+
+    #[test]
+    fn set_speed_reapplies_flow_control_and_framing_after_baud_change() {
+        let target = Rc::new(FakeTarget::new(115_200, &[115_200, 921_600]));
+        let framing = UartFraming {
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        };
+        let rescue = RescueSerialBuilder::new(target.clone())
+            .flow_control(true)
+            .framing(framing)
+            .build()
+            .expect("build should succeed");
+
+        rescue.set_speed(921_600).expect("set_speed should succeed");
+
+        assert_eq!(*target.flow_control_calls.borrow(), vec![true, true]);
+        assert_eq!(*target.framing_calls.borrow(), vec![framing, framing]);
+    }
+
+    #[test]
+    fn set_speed_reverts_when_the_link_cannot_sustain_the_new_rate() {
+        let target = Rc::new(FakeTarget::with_unsustainable(
+            115_200,
+            &[115_200, 460_800],
+            &[460_800],
+        ));
+        let rescue = RescueSerial::new(target.clone());
+
+        let result = rescue.set_speed(460_800);
+
+        assert!(result.is_err());
+        assert_eq!(target.get_baudrate().unwrap(), 115_200);
+    }
+
+    #[test]
+    fn negotiate_max_speed_skips_a_rate_that_acks_but_cannot_sustain_confirmation() {
+        let target = FakeTarget::with_unsustainable(
+            115_200,
+            &[115_200, 921_600, 460_800],
+            &[921_600],
+        );
+        let rescue = RescueSerial::new(Rc::new(target));
+        let negotiated = rescue
+            .negotiate_max_speed()
+            .expect("negotiation should succeed");
+        assert_eq!(negotiated, 460_800);
+    }
+}
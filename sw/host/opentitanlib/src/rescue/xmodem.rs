@@ -0,0 +1,795 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use std::time::{Duration, Instant};
+
+use crate::io::uart::Uart;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const CRC: u8 = b'C';
+const BLOCK_SIZE: usize = 128;
+const BLOCK_SIZE_1K: usize = 1024;
+const CPMEOF: u8 = 0x1a;
+
+/// Number of times a 1K block may be NAKed before `send` gives up on the
+/// larger block size and falls back to plain 128-byte XMODEM.
+const BLOCK_1K_RETRIES: u32 = 2;
+
+/// Number of times a plain 128-byte block may be NAKed (e.g. by a CRC
+/// mismatch the receiver detected) before `send` gives up on the transfer
+/// entirely. There's no smaller block size to fall back to, so this is a
+/// conventional XMODEM retry count rather than a fallback trigger.
+const BLOCK_RETRIES: u32 = 10;
+
+/// Bound on how long to wait for the rest of an in-flight block or reply
+/// byte. `Uart::read` is non-blocking, so every multi-byte read below has to
+/// poll for it.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+enum SendOutcome {
+    Acked,
+    Naked,
+}
+
+/// A minimal XMODEM/CRC implementation used to move firmware images over the
+/// rescue UART link, with optional XMODEM-1K and YMODEM-style batch framing.
+pub struct Xmodem {
+    block_size: usize,
+    ymodem: bool,
+}
+
+impl Default for Xmodem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Xmodem {
+    pub fn new() -> Self {
+        Xmodem {
+            block_size: BLOCK_SIZE,
+            ymodem: false,
+        }
+    }
+
+    /// Uses `block_size`-byte data blocks (128 for plain XMODEM, 1024 for
+    /// XMODEM-1K). `send` will still fall back to 128-byte blocks if the
+    /// receiver NAKs the larger size.
+    pub fn with_block_size(block_size: usize) -> Self {
+        Xmodem {
+            block_size,
+            ymodem: false,
+        }
+    }
+
+    /// Uses 1024-byte blocks and prefixes the transfer with a YMODEM batch
+    /// header block carrying the filename and exact payload length, so
+    /// `receive` can recover the real length instead of trimming CPMEOF
+    /// padding from the final block. `send` falls back to a headerless,
+    /// 128-byte-block transfer if the receiver NAKs the batch header, and to
+    /// 128-byte blocks after an accepted header if the receiver NAKs the 1K
+    /// block size; `receive` accepts a batch header but does not require
+    /// one.
+    pub fn ymodem() -> Self {
+        Xmodem {
+            block_size: BLOCK_SIZE_1K,
+            ymodem: true,
+        }
+    }
+
+    /// Reads exactly `buf.len()` bytes from `uart`, polling `Uart::read`
+    /// (documented as non-blocking) until the buffer fills or `READ_TIMEOUT`
+    /// elapses since the last byte arrived.
+    fn read_exact(uart: &dyn Uart, buf: &mut [u8]) -> Result<()> {
+        let mut filled = 0;
+        let mut deadline = Instant::now() + READ_TIMEOUT;
+        while filled < buf.len() {
+            let n = uart.read(&mut buf[filled..])?;
+            if n > 0 {
+                filled += n;
+                deadline = Instant::now() + READ_TIMEOUT;
+                continue;
+            }
+            if Instant::now() >= deadline {
+                bail!(
+                    "timed out waiting for {} bytes, got {filled}",
+                    buf.len()
+                );
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Sends `data` to `uart`, negotiating the largest block size this
+    /// instance was configured with. Falls back to a plain, headerless,
+    /// 128-byte-block transfer if the receiver NAKs the YMODEM batch header,
+    /// and to 128-byte blocks for the rest of the data if it repeatedly NAKs
+    /// a larger block.
+    pub fn send(&self, uart: &dyn Uart, data: &[u8]) -> Result<()> {
+        let mut start = [0u8; 1];
+        Self::read_exact(uart, &mut start)?;
+        if start[0] != CRC {
+            bail!("expected CRC handshake byte, got {:#x}", start[0]);
+        }
+
+        let mut block_size = self.block_size;
+        if self.ymodem {
+            match self.send_header_block(uart, "firmware", data.len(), block_size)? {
+                SendOutcome::Acked => {}
+                SendOutcome::Naked => {
+                    log::warn!(
+                        "target NAKed the YMODEM batch header, falling back to plain {BLOCK_SIZE}-byte XMODEM"
+                    );
+                    block_size = BLOCK_SIZE;
+                }
+            }
+        }
+
+        let mut block_num: u8 = 1;
+        let mut offset = 0;
+        while offset < data.len() {
+            let end = (offset + block_size).min(data.len());
+            let retries = if block_size > BLOCK_SIZE {
+                BLOCK_1K_RETRIES
+            } else {
+                BLOCK_RETRIES
+            };
+            let mut outcome = SendOutcome::Naked;
+            for _ in 0..retries {
+                outcome = self.send_block(uart, block_num, &data[offset..end], block_size)?;
+                if matches!(outcome, SendOutcome::Acked) {
+                    break;
+                }
+            }
+            match outcome {
+                SendOutcome::Acked => {
+                    offset = end;
+                    block_num = block_num.wrapping_add(1);
+                }
+                SendOutcome::Naked if block_size > BLOCK_SIZE => {
+                    log::warn!(
+                        "target NAKed {block_size}-byte block, falling back to {BLOCK_SIZE}-byte blocks"
+                    );
+                    block_size = BLOCK_SIZE;
+                }
+                SendOutcome::Naked => bail!("target repeatedly NAKed block {block_num}"),
+            }
+        }
+
+        uart.write(&[EOT])?;
+        let mut reply = [0u8; 1];
+        Self::read_exact(uart, &mut reply)?;
+        if reply[0] != ACK {
+            bail!("target did not ACK end of transfer");
+        }
+        Ok(())
+    }
+
+    fn send_block(
+        &self,
+        uart: &dyn Uart,
+        block_num: u8,
+        chunk: &[u8],
+        block_size: usize,
+    ) -> Result<SendOutcome> {
+        let marker = if block_size > BLOCK_SIZE { STX } else { SOH };
+        let mut block = vec![CPMEOF; block_size];
+        block[..chunk.len()].copy_from_slice(chunk);
+
+        let mut packet = Vec::with_capacity(block_size + 5);
+        packet.push(marker);
+        packet.push(block_num);
+        packet.push(!block_num);
+        packet.extend_from_slice(&block);
+        let crc = crc16(&block);
+        packet.extend_from_slice(&crc.to_be_bytes());
+        uart.write(&packet)?;
+
+        let mut reply = [0u8; 1];
+        Self::read_exact(uart, &mut reply)?;
+        match reply[0] {
+            ACK => Ok(SendOutcome::Acked),
+            NAK => Ok(SendOutcome::Naked),
+            CAN => bail!("transfer cancelled by receiver"),
+            _ => bail!("unexpected reply {:#x} to block {block_num}", reply[0]),
+        }
+    }
+
+    /// Sends the YMODEM batch header (block 0) carrying `name` and the exact
+    /// payload length, so the receiver can size its buffer precisely.
+    /// Returns `SendOutcome::Naked` rather than erroring if the receiver
+    /// rejects the header outright, so the caller can fall back cleanly.
+    fn send_header_block(
+        &self,
+        uart: &dyn Uart,
+        name: &str,
+        len: usize,
+        block_size: usize,
+    ) -> Result<SendOutcome> {
+        let marker = if block_size > BLOCK_SIZE { STX } else { SOH };
+        let mut payload = Vec::with_capacity(block_size);
+        payload.extend_from_slice(name.as_bytes());
+        payload.push(0);
+        payload.extend_from_slice(len.to_string().as_bytes());
+        payload.resize(block_size, 0);
+
+        let mut packet = Vec::with_capacity(block_size + 5);
+        packet.push(marker);
+        packet.push(0);
+        packet.push(0xff);
+        packet.extend_from_slice(&payload);
+        let crc = crc16(&payload);
+        packet.extend_from_slice(&crc.to_be_bytes());
+        uart.write(&packet)?;
+
+        let mut reply = [0u8; 1];
+        Self::read_exact(uart, &mut reply)?;
+        match reply[0] {
+            ACK => Ok(SendOutcome::Acked),
+            NAK => Ok(SendOutcome::Naked),
+            CAN => bail!("transfer cancelled by receiver"),
+            _ => bail!("unexpected reply {:#x} to YMODEM header block", reply[0]),
+        }
+    }
+
+    /// Receives a transfer from `uart`, appending the payload to `data`. A
+    /// YMODEM batch header (block 0) is recognized and consumed if the
+    /// sender includes one, giving the exact payload length; otherwise
+    /// CPMEOF padding on the final block is trimmed. Each block's CRC and
+    /// block-number complement are checked and mismatches are NAKed for
+    /// retransmission. A duplicate of the previously-accepted block (the
+    /// usual symptom of our ACK getting lost) is ACKed again rather than
+    /// appended twice.
+    pub fn receive(&self, uart: &dyn Uart, data: &mut Vec<u8>) -> Result<()> {
+        uart.write(&[CRC])?;
+
+        let mut exact_len = None;
+        let mut expected_block: u8 = 1;
+        let mut saw_header = false;
+        loop {
+            let mut marker = [0u8; 1];
+            Self::read_exact(uart, &mut marker)?;
+            let block_size = match marker[0] {
+                EOT => {
+                    uart.write(&[ACK])?;
+                    break;
+                }
+                SOH => BLOCK_SIZE,
+                STX => BLOCK_SIZE_1K,
+                _ => {
+                    uart.write(&[NAK])?;
+                    continue;
+                }
+            };
+
+            let mut rest = vec![0u8; block_size + 4];
+            Self::read_exact(uart, &mut rest)?;
+            let block_num = rest[0];
+            let complement = rest[1];
+            let block = &rest[2..2 + block_size];
+            let received_crc = u16::from_be_bytes([rest[2 + block_size], rest[3 + block_size]]);
+            if crc16(block) != received_crc || complement != !block_num {
+                uart.write(&[NAK])?;
+                continue;
+            }
+
+            if block_num == 0 && !saw_header && expected_block == 1 {
+                // A YMODEM batch header, only valid as the very first block.
+                exact_len = parse_header_block(block);
+                saw_header = true;
+                uart.write(&[ACK])?;
+                continue;
+            }
+
+            if block_num == expected_block.wrapping_sub(1) {
+                // A duplicate of the block we already appended: the sender
+                // never saw our ACK and retransmitted it. ACK it again
+                // without appending it twice.
+                uart.write(&[ACK])?;
+                continue;
+            }
+
+            if block_num != expected_block {
+                uart.write(&[NAK])?;
+                continue;
+            }
+            data.extend_from_slice(block);
+            uart.write(&[ACK])?;
+            expected_block = expected_block.wrapping_add(1);
+        }
+
+        match exact_len {
+            Some(len) => data.truncate(len),
+            None => {
+                while matches!(data.last(), Some(&CPMEOF)) {
+                    data.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a YMODEM batch header payload (NUL-separated filename and decimal
+/// length, zero-padded to the block size) and returns the advertised length.
+fn parse_header_block(payload: &[u8]) -> Option<usize> {
+    let fields: Vec<&[u8]> = payload.splitn(3, |&b| b == 0).collect();
+    let len_field = fields.get(1)?;
+    let len_field = String::from_utf8_lossy(len_field);
+    let len_field = len_field.trim();
+    if len_field.is_empty() {
+        return None;
+    }
+    len_field.parse::<usize>().ok()
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// An in-memory `Uart` pair: writes to one side become reads on the
+    /// other, so a `Xmodem::send`/`receive` pair can be exercised directly
+    /// against each other without real hardware. Shared across threads, so
+    /// the queues are mutex-protected rather than using a `RefCell`.
+    struct MockUart {
+        inbox: Mutex<VecDeque<u8>>,
+        outbox: Mutex<VecDeque<u8>>,
+    }
+
+    impl MockUart {
+        fn new_pair() -> (Self, Self) {
+            let a_to_b = Mutex::new(VecDeque::new());
+            let b_to_a = Mutex::new(VecDeque::new());
+            (
+                MockUart {
+                    inbox: Mutex::new(VecDeque::new()),
+                    outbox: a_to_b,
+                },
+                MockUart {
+                    inbox: Mutex::new(VecDeque::new()),
+                    outbox: b_to_a,
+                },
+            )
+        }
+    }
+
+    impl Uart for MockUart {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut inbox = self.inbox.lock().unwrap();
+            let n = inbox.len().min(buf.len());
+            for (i, byte) in inbox.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, buf: &[u8]) -> Result<()> {
+            self.outbox.lock().unwrap().extend(buf.iter().copied());
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(115200)
+        }
+
+        fn set_baudrate(&self, _baudrate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Shuttles bytes sitting in each side's outbox into the other side's
+    /// inbox, so each can observe what the other wrote.
+    fn pump(a: &MockUart, b: &MockUart) {
+        a.inbox
+            .lock()
+            .unwrap()
+            .extend(b.outbox.lock().unwrap().drain(..));
+        b.inbox
+            .lock()
+            .unwrap()
+            .extend(a.outbox.lock().unwrap().drain(..));
+    }
+
+    /// Fills `buf` from what `receiver` writes back to `sender`, pumping the
+    /// pair while waiting, since nothing else moves the receiver's replies
+    /// across in the tests that drive the receiver by hand.
+    fn read_from_receiver(sender: &MockUart, receiver: &MockUart, buf: &mut [u8]) {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let mut filled = 0;
+        while filled < buf.len() {
+            pump(sender, receiver);
+            filled += sender.read(&mut buf[filled..]).unwrap();
+            assert!(Instant::now() < deadline, "timed out waiting for the receiver");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Runs `send` and `receive` concurrently (on real threads, since each
+    /// blocks on the other) against a connected pair of `MockUart`s, pumping
+    /// bytes between them until both finish.
+    fn round_trip(send_xm: Xmodem, recv_xm: Xmodem, data: &[u8]) -> Vec<u8> {
+        let (sender, receiver) = MockUart::new_pair();
+        let mut received = Vec::new();
+        std::thread::scope(|scope| {
+            let send_handle = scope.spawn(|| send_xm.send(&sender, data));
+            let recv_handle = scope.spawn(|| recv_xm.receive(&receiver, &mut received));
+            loop {
+                pump(&sender, &receiver);
+                if send_handle.is_finished() && recv_handle.is_finished() {
+                    pump(&sender, &receiver);
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            send_handle.join().unwrap().expect("send failed");
+            recv_handle.join().unwrap().expect("receive failed");
+        });
+        received
+    }
+
+    #[test]
+    fn plain_xmodem_round_trip() {
+        let data = vec![0x42u8; 300];
+        let received = round_trip(Xmodem::new(), Xmodem::new(), &data);
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn xmodem_1k_round_trip() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(3000).collect();
+        let received = round_trip(
+            Xmodem::with_block_size(1024),
+            Xmodem::with_block_size(1024),
+            &data,
+        );
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn ymodem_round_trip_recovers_exact_length() {
+        // Not a multiple of the block size, and the last byte (2330 % 256)
+        // is CPMEOF itself, so a correct length requires the batch header
+        // rather than CPMEOF trimming.
+        let data: Vec<u8> = (0..=255u8).cycle().take(2331).collect();
+        let received = round_trip(Xmodem::ymodem(), Xmodem::ymodem(), &data);
+        assert_eq!(received, data);
+    }
+
+    /// Emulates a legacy, non-YMODEM-aware receiver: NAKs the batch header
+    /// block outright and otherwise ACKs plain 128-byte XMODEM blocks.
+    struct LegacyReceiver {
+        reply: Mutex<VecDeque<u8>>,
+        header_attempts: Mutex<u32>,
+        stx_data_blocks: Mutex<u32>,
+        soh_data_blocks: Mutex<u32>,
+    }
+
+    impl LegacyReceiver {
+        fn new() -> Self {
+            LegacyReceiver {
+                reply: Mutex::new(VecDeque::from([CRC])),
+                header_attempts: Mutex::new(0),
+                stx_data_blocks: Mutex::new(0),
+                soh_data_blocks: Mutex::new(0),
+            }
+        }
+    }
+
+    impl Uart for LegacyReceiver {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut reply = self.reply.lock().unwrap();
+            let n = reply.len().min(buf.len());
+            for (i, byte) in reply.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, buf: &[u8]) -> Result<()> {
+            let mut reply = self.reply.lock().unwrap();
+            match buf[0] {
+                EOT => reply.push_back(ACK),
+                STX if buf[1] == 0 => {
+                    // The YMODEM batch header: this receiver doesn't know
+                    // what to do with it.
+                    *self.header_attempts.lock().unwrap() += 1;
+                    reply.push_back(NAK);
+                }
+                STX => {
+                    *self.stx_data_blocks.lock().unwrap() += 1;
+                    reply.push_back(ACK);
+                }
+                SOH => {
+                    *self.soh_data_blocks.lock().unwrap() += 1;
+                    reply.push_back(ACK);
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(115200)
+        }
+
+        fn set_baudrate(&self, _baudrate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Emulates a receiver that NAKs the first copy of each data block (as a
+    /// CRC error would) but ACKs every retransmission.
+    struct FlakyOnceReceiver {
+        reply: Mutex<VecDeque<u8>>,
+        block_attempts: Mutex<u32>,
+    }
+
+    impl FlakyOnceReceiver {
+        fn new() -> Self {
+            FlakyOnceReceiver {
+                reply: Mutex::new(VecDeque::from([CRC])),
+                block_attempts: Mutex::new(0),
+            }
+        }
+    }
+
+    impl Uart for FlakyOnceReceiver {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut reply = self.reply.lock().unwrap();
+            let n = reply.len().min(buf.len());
+            for (i, byte) in reply.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, buf: &[u8]) -> Result<()> {
+            let mut reply = self.reply.lock().unwrap();
+            match buf[0] {
+                EOT => reply.push_back(ACK),
+                SOH => {
+                    let mut attempts = self.block_attempts.lock().unwrap();
+                    *attempts += 1;
+                    if *attempts == 1 {
+                        reply.push_back(NAK);
+                    } else {
+                        reply.push_back(ACK);
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(115200)
+        }
+
+        fn set_baudrate(&self, _baudrate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn send_retransmits_a_naked_128_byte_block_before_giving_up() {
+        let data = vec![0x7eu8; 50];
+        let receiver = FlakyOnceReceiver::new();
+        Xmodem::new()
+            .send(&receiver, &data)
+            .expect("send should retransmit after a single NAK instead of aborting");
+        assert_eq!(*receiver.block_attempts.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn ymodem_sender_falls_back_when_receiver_has_no_header_support() {
+        let data = vec![0xa5u8; 300];
+        let receiver = LegacyReceiver::new();
+        Xmodem::ymodem()
+            .send(&receiver, &data)
+            .expect("send should fall back instead of failing");
+
+        assert_eq!(*receiver.header_attempts.lock().unwrap(), 1);
+        assert_eq!(*receiver.stx_data_blocks.lock().unwrap(), 0);
+        assert!(*receiver.soh_data_blocks.lock().unwrap() > 0);
+    }
+
+    #[test]
+    fn receive_naks_corrupted_crc() {
+        let (sender, receiver) = MockUart::new_pair();
+        let mut received = Vec::new();
+        let xm = Xmodem::new();
+
+        let mut block = vec![CPMEOF; BLOCK_SIZE];
+        block[0] = 0x55;
+
+        std::thread::scope(|scope| {
+            let recv_handle = scope.spawn(|| xm.receive(&receiver, &mut received));
+
+            // Drive the receiver by hand: consume its initial 'C', then feed
+            // it one corrupted block followed by one good one.
+            pump(&sender, &receiver);
+            let mut start = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut start);
+            assert_eq!(start[0], CRC);
+
+            let mut bad_packet = vec![SOH, 1, !1];
+            bad_packet.extend_from_slice(&block);
+            bad_packet.extend_from_slice(&0u16.to_be_bytes()); // wrong CRC
+            sender.write(&bad_packet).unwrap();
+            pump(&sender, &receiver);
+
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], NAK, "corrupted block should be NAKed");
+
+            let mut good_packet = vec![SOH, 1, !1];
+            good_packet.extend_from_slice(&block);
+            good_packet.extend_from_slice(&crc16(&block).to_be_bytes());
+            sender.write(&good_packet).unwrap();
+            pump(&sender, &receiver);
+
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            sender.write(&[EOT]).unwrap();
+            pump(&sender, &receiver);
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            recv_handle.join().unwrap().expect("receive failed");
+        });
+
+        let mut want = block.clone();
+        while matches!(want.last(), Some(&CPMEOF)) {
+            want.pop();
+        }
+        assert_eq!(received, want);
+    }
+
+    #[test]
+    fn receive_naks_bad_complement_byte() {
+        let (sender, receiver) = MockUart::new_pair();
+        let mut received = Vec::new();
+        let xm = Xmodem::new();
+
+        let mut block = vec![CPMEOF; BLOCK_SIZE];
+        block[0] = 0x33;
+
+        std::thread::scope(|scope| {
+            let recv_handle = scope.spawn(|| xm.receive(&receiver, &mut received));
+
+            // Drive the receiver by hand: consume its initial 'C', then feed
+            // it one block with a bad complement byte followed by a good one.
+            pump(&sender, &receiver);
+            let mut start = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut start);
+            assert_eq!(start[0], CRC);
+
+            let mut bad_packet = vec![SOH, 1, 0]; // should be !1
+            bad_packet.extend_from_slice(&block);
+            bad_packet.extend_from_slice(&crc16(&block).to_be_bytes());
+            sender.write(&bad_packet).unwrap();
+            pump(&sender, &receiver);
+
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], NAK, "bad complement byte should be NAKed");
+
+            let mut good_packet = vec![SOH, 1, !1];
+            good_packet.extend_from_slice(&block);
+            good_packet.extend_from_slice(&crc16(&block).to_be_bytes());
+            sender.write(&good_packet).unwrap();
+            pump(&sender, &receiver);
+
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            sender.write(&[EOT]).unwrap();
+            pump(&sender, &receiver);
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            recv_handle.join().unwrap().expect("receive failed");
+        });
+
+        let mut want = block.clone();
+        while matches!(want.last(), Some(&CPMEOF)) {
+            want.pop();
+        }
+        assert_eq!(received, want);
+    }
+
+    #[test]
+    fn receive_acks_duplicate_retransmission_without_duplicating_data() {
+        let (sender, receiver) = MockUart::new_pair();
+        let mut received = Vec::new();
+        let xm = Xmodem::new();
+
+        let mut block = vec![CPMEOF; BLOCK_SIZE];
+        block[0] = 0xaa;
+
+        std::thread::scope(|scope| {
+            let recv_handle = scope.spawn(|| xm.receive(&receiver, &mut received));
+
+            pump(&sender, &receiver);
+            let mut start = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut start);
+            assert_eq!(start[0], CRC);
+
+            let mut packet = vec![SOH, 1, !1];
+            packet.extend_from_slice(&block);
+            packet.extend_from_slice(&crc16(&block).to_be_bytes());
+
+            // Send block 1, then send it again as if our ACK was lost in
+            // transit and the sender retransmitted it.
+            sender.write(&packet).unwrap();
+            pump(&sender, &receiver);
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            sender.write(&packet).unwrap();
+            pump(&sender, &receiver);
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK, "duplicate block should still be ACKed");
+
+            sender.write(&[EOT]).unwrap();
+            pump(&sender, &receiver);
+            let mut reply = [0u8; 1];
+            read_from_receiver(&sender, &receiver, &mut reply);
+            assert_eq!(reply[0], ACK);
+
+            recv_handle.join().unwrap().expect("receive failed");
+        });
+
+        let mut want = block.clone();
+        while matches!(want.last(), Some(&CPMEOF)) {
+            want.pop();
+        }
+        assert_eq!(received, want, "duplicate block must not be appended twice");
+    }
+}
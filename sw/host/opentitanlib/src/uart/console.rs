@@ -0,0 +1,261 @@
+// Copyright lowRISC contributors (OpenTitan project).
+// Licensed under the Apache License, Version 2.0, see LICENSE for details.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::time::{Duration, Instant};
+
+use crate::io::uart::Uart;
+
+/// Helpers for driving a request/response style console over a `Uart`.
+pub struct UartConsole;
+
+impl UartConsole {
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Reads from `uart` until `pattern` matches the accumulated output, or
+    /// `timeout` elapses. Returns the regex captures from the match: the
+    /// whole match first, then each capture group in order.
+    pub fn wait_for(uart: &dyn Uart, pattern: &str, timeout: Duration) -> Result<Vec<String>> {
+        let re = Regex::new(pattern).context("invalid console pattern")?;
+        let deadline = Instant::now() + timeout;
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 256];
+        loop {
+            let n = uart.read(&mut chunk)?;
+            if n > 0 {
+                buf.extend_from_slice(&chunk[..n]);
+                let text = String::from_utf8_lossy(&buf);
+                if let Some(captures) = re.captures(&text) {
+                    return Ok(captures
+                        .iter()
+                        .map(|m| m.map(|m| m.as_str().to_string()).unwrap_or_default())
+                        .collect());
+                }
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!("timed out waiting for pattern {:?}", pattern);
+            }
+            std::thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+
+    /// Reads from `uart` until the line has gone idle for roughly two
+    /// character times at the UART's currently configured baud rate, then
+    /// matches `pattern` against the accumulated output. This avoids
+    /// guessing a fixed timeout for variable-length responses: one frame is
+    /// 10 bit-times (1 start + 8 data + 1 stop), so two character times is
+    /// `20_000_000 / baud` microseconds. `cap` bounds the overall wait in
+    /// case the target never replies at all. Returns the regex captures in
+    /// the same order as `wait_for`: the whole match first.
+    ///
+    /// An idle gap doesn't necessarily mean the response is complete — the
+    /// target may pause mid-response (e.g. between a `mode: ...\r\n` line
+    /// and the `ok: ...\r\n` that follows). So each idle gap is treated as a
+    /// checkpoint: if `pattern` matches what's been read so far, we're done;
+    /// otherwise we keep reading until it does or `cap` elapses.
+    pub fn wait_for_idle(uart: &dyn Uart, pattern: &str, cap: Duration) -> Result<Vec<String>> {
+        let re = Regex::new(pattern).context("invalid console pattern")?;
+        let baud = uart.get_baudrate()?;
+        let idle_threshold = Duration::from_micros(20_000_000 / baud as u64);
+        // Never poll slower than the idle gap we're trying to detect, or
+        // the baud-derived threshold is dead weight: at 1.5Mbaud it's 13us,
+        // far below a 10ms fixed poll, so idle would never be observed
+        // before the next byte (or the `cap` timeout) arrives anyway.
+        let poll_interval = idle_threshold.min(Self::POLL_INTERVAL);
+
+        let start = Instant::now();
+        let mut idle_deadline: Option<Instant> = None;
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 256];
+        loop {
+            let n = uart.read(&mut chunk)?;
+            if n > 0 {
+                buf.extend_from_slice(&chunk[..n]);
+                idle_deadline = Some(Instant::now() + idle_threshold);
+            } else if idle_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                let text = String::from_utf8_lossy(&buf);
+                if let Some(captures) = re.captures(&text) {
+                    return Ok(captures
+                        .iter()
+                        .map(|m| m.map(|m| m.as_str().to_string()).unwrap_or_default())
+                        .collect());
+                }
+                // Idle, but the pattern hasn't matched yet: this was a pause
+                // mid-response, not the end of it. Keep reading.
+                idle_deadline = None;
+            }
+            if Instant::now() - start >= cap {
+                anyhow::bail!("timed out waiting for pattern {:?}", pattern);
+            }
+            std::thread::sleep(poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// A `Uart` that reports `baudrate` and stays silent for `start_delay`
+    /// before handing out `reply` one byte at a time.
+    struct DelayedUart {
+        baudrate: u32,
+        start: Instant,
+        start_delay: Duration,
+        reply: Mutex<VecDeque<u8>>,
+    }
+
+    impl DelayedUart {
+        fn new(baudrate: u32, start_delay: Duration, reply: &[u8]) -> Self {
+            DelayedUart {
+                baudrate,
+                start: Instant::now(),
+                start_delay,
+                reply: Mutex::new(reply.iter().copied().collect()),
+            }
+        }
+    }
+
+    impl Uart for DelayedUart {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            if self.start.elapsed() < self.start_delay {
+                return Ok(0);
+            }
+            let mut reply = self.reply.lock().unwrap();
+            let n = reply.len().min(buf.len());
+            for (i, byte) in reply.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, _buf: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(self.baudrate)
+        }
+
+        fn set_baudrate(&self, _baudrate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wait_for_idle_tolerates_slow_first_reply() {
+        // At 1Mbaud the idle threshold is 20us, far shorter than the delay
+        // before the target starts replying at all. A premature deadline
+        // armed before any byte arrives would bail out empty-handed.
+        let uart = DelayedUart::new(1_000_000, Duration::from_millis(50), b"ok: foo\r\n");
+        let result =
+            UartConsole::wait_for_idle(&uart, r"(ok|error):.*\r\n", Duration::from_millis(500))
+                .expect("should wait past the slow start instead of bailing immediately");
+        assert_eq!(result[1], "ok");
+    }
+
+    #[test]
+    fn wait_for_idle_times_out_when_target_never_replies() {
+        let uart = DelayedUart::new(115200, Duration::from_secs(10), b"");
+        let result =
+            UartConsole::wait_for_idle(&uart, r"(ok|error):.*\r\n", Duration::from_millis(50));
+        assert!(result.is_err());
+    }
+
+    /// A `Uart` that replies in two pieces with a pause of silence between
+    /// them, to exercise idle detection against a mid-response gap rather
+    /// than just the end of the response.
+    struct PausedReplyUart {
+        baudrate: u32,
+        pause: Duration,
+        first: Mutex<VecDeque<u8>>,
+        second: Mutex<VecDeque<u8>>,
+        second_release_at: Mutex<Option<Instant>>,
+    }
+
+    impl PausedReplyUart {
+        fn new(baudrate: u32, first: &[u8], pause: Duration, second: &[u8]) -> Self {
+            PausedReplyUart {
+                baudrate,
+                pause,
+                first: Mutex::new(first.iter().copied().collect()),
+                second: Mutex::new(second.iter().copied().collect()),
+                second_release_at: Mutex::new(None),
+            }
+        }
+    }
+
+    impl Uart for PausedReplyUart {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let mut first = self.first.lock().unwrap();
+            if !first.is_empty() {
+                let n = first.len().min(buf.len());
+                for (i, byte) in first.drain(..n).enumerate() {
+                    buf[i] = byte;
+                }
+                if first.is_empty() {
+                    *self.second_release_at.lock().unwrap() = Some(Instant::now() + self.pause);
+                }
+                return Ok(n);
+            }
+            drop(first);
+
+            let release_at = *self.second_release_at.lock().unwrap();
+            if release_at.map(|t| Instant::now() < t).unwrap_or(true) {
+                return Ok(0);
+            }
+            let mut second = self.second.lock().unwrap();
+            let n = second.len().min(buf.len());
+            for (i, byte) in second.drain(..n).enumerate() {
+                buf[i] = byte;
+            }
+            Ok(n)
+        }
+
+        fn write(&self, _buf: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_baudrate(&self) -> Result<u32> {
+            Ok(self.baudrate)
+        }
+
+        fn set_baudrate(&self, _baudrate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_break(&self, _enable: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wait_for_idle_tolerates_pause_mid_response() {
+        // At 1Mbaud the idle threshold is 20us, far shorter than the 30ms
+        // pause the target takes between the `mode: ...` line and the
+        // `ok: ...` line that follows it. Treating that gap as "done" would
+        // make the pattern fail to match a response it will fully deliver
+        // if given a little more time.
+        let uart = PausedReplyUart::new(
+            1_000_000,
+            b"mode: BAUD\r\n",
+            Duration::from_millis(30),
+            b"ok: mode\r\n",
+        );
+        let result = UartConsole::wait_for_idle(
+            &uart,
+            r"mode: BAUD\r\n(ok|error): mode\r\n",
+            Duration::from_millis(200),
+        )
+        .expect("should keep reading past the mid-response pause instead of bailing early");
+        assert_eq!(result[1], "ok");
+    }
+}